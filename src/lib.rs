@@ -1,31 +1,106 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fs;
 use std::fs::{File, OpenOptions};
 use std::io;
 use std::io::prelude::*;
 use std::io::{BufReader, SeekFrom};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 use serde_json;
+use sha2::{Digest, Sha256};
+
+type ValueHash = [u8; 32];
 
 #[derive(Serialize, Deserialize)]
 enum Command {
-    Set { key: String, value: String },
-    Rm { key: String },
+    Set {
+        key: String,
+        #[serde(with = "hex_hash")]
+        value_ref: ValueHash,
+    },
+    Rm {
+        key: String,
+    },
+}
+
+const TAG_SET: u8 = 0;
+const TAG_RM: u8 = 1;
+const TAG_BLOB: u8 = 2;
+const RECORD_HEADER_LEN: u64 = 5;
+
+fn hash_value(bytes: &[u8]) -> ValueHash {
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    hasher.finalize().into()
+}
+
+fn hash_to_hex(hash: &ValueHash) -> String {
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
 }
 
+fn hash_from_hex(s: &str) -> Option<ValueHash> {
+    if s.len() != 64 {
+        return None;
+    }
+
+    let mut hash = [0u8; 32];
+
+    for (i, chunk) in s.as_bytes().chunks(2).enumerate() {
+        hash[i] = u8::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok()?;
+    }
+
+    Some(hash)
+}
+
+// `Command::Set::value_ref` is serialized as a hex string instead of serde's
+// default array-of-ints encoding for `[u8; 32]`, which would cost ~115 bytes
+// per record instead of 66.
+mod hex_hash {
+    use super::ValueHash;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(hash: &ValueHash, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&super::hash_to_hex(hash))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<ValueHash, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+
+        super::hash_from_hex(&s).ok_or_else(|| serde::de::Error::custom("invalid value_ref hex"))
+    }
+}
+
+const ARCHIVE_MAGIC: &[u8; 4] = b"KVA1";
+const ARCHIVE_HEADER_LEN: u64 = 8;
+const ARCHIVE_ENTRY_LEN: u64 = 24;
+
 impl fmt::Debug for Command {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", serde_json::to_string_pretty(&self).unwrap())
     }
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum BulkCommand {
+    Set { key: String, value: String },
+    Rm { key: String },
+}
+
 #[derive(Debug)]
 pub enum KvError {
     IoError(io::Error),
     JsonError(serde_json::Error),
+    Utf8Error(std::string::FromUtf8Error),
+    InvalidArchive,
     NotImplemented,
 }
 
@@ -41,12 +116,32 @@ impl From<serde_json::Error> for KvError {
     }
 }
 
+impl From<std::string::FromUtf8Error> for KvError {
+    fn from(error: std::string::FromUtf8Error) -> Self {
+        KvError::Utf8Error(error)
+    }
+}
+
 pub type KvResult<T> = Result<T, KvError>;
 
+type BuildIndexResult = (HashMap<String, ValueHash>, HashMap<ValueHash, (u64, u64)>, usize);
+type CompactedLog = (Vec<u8>, HashMap<ValueHash, (u64, u64)>);
+
+#[derive(Debug)]
+pub struct Stats {
+    pub live_keys: usize,
+    pub log_size: u64,
+    pub live_bytes: u64,
+    pub reclaimable_bytes: u64,
+    pub garbage_fraction: f64,
+}
+
 pub struct KvStore {
+    dir_path: PathBuf,
     log: File,
     log_length: usize,
-    index: HashMap<String, (u64, u64)>,
+    index: HashMap<String, ValueHash>,
+    value_locations: HashMap<ValueHash, (u64, u64)>,
     pub compaction_threshold: usize,
 }
 
@@ -63,23 +158,31 @@ impl KvStore {
             .append(true)
             .open(&path)?;
 
-        let (index, log_length) = KvStore::build_index(&log)?;
+        let (index, value_locations, log_length) = KvStore::build_index(&log)?;
 
         Ok(KvStore {
+            dir_path,
             log,
             index,
+            value_locations,
             log_length,
             compaction_threshold: 100,
         })
     }
 
     pub fn set(&mut self, key: String, value: String) -> KvResult<()> {
-        let offsets = self.append_command(Command::Set {
+        let hash = hash_value(value.as_bytes());
+
+        if !self.value_locations.contains_key(&hash) {
+            let span = self.append_blob(value.as_bytes())?;
+            self.value_locations.insert(hash, span);
+        }
+
+        self.append_command(Command::Set {
             key: key.clone(),
-            value,
+            value_ref: hash,
         })?;
-
-        self.index.insert(key, offsets);
+        self.index.insert(key, hash);
 
         if self.log_length >= self.compaction_threshold {
             self.compact()?;
@@ -89,21 +192,18 @@ impl KvStore {
     }
 
     pub fn get(&mut self, key: String) -> KvResult<Option<String>> {
-        match self.index.get(&key) {
-            None => Ok(None),
-            Some((i0, i1)) => {
-                let length = (i1 - i0) as usize;
-                let mut buf = vec![0u8; length];
-
-                self.log.seek(SeekFrom::Start(*i0))?;
-                self.log.read_exact(&mut buf)?;
-
-                match serde_json::from_slice(&buf)? {
-                    Command::Set { value, .. } => Ok(Some(value.to_string())),
-                    Command::Rm { .. } => Ok(None),
-                }
-            }
-        }
+        let hash = match self.index.get(&key) {
+            None => return Ok(None),
+            Some(hash) => *hash,
+        };
+        let (i0, i1) = self.value_locations[&hash];
+        let length = (i1 - i0) as usize;
+        let mut buf = vec![0u8; length];
+
+        self.log.seek(SeekFrom::Start(i0))?;
+        self.log.read_exact(&mut buf)?;
+
+        Ok(Some(String::from_utf8(buf)?))
     }
 
     pub fn remove(&mut self, key: String) -> KvResult<()> {
@@ -113,65 +213,339 @@ impl KvStore {
         Ok(())
     }
 
-    fn append_command(&mut self, command: Command) -> KvResult<(u64, u64)> {
-        let json = serde_json::to_string(&command)?;
-        let start_offset = self.log.seek(SeekFrom::End(0))?;
+    pub fn stats(&self) -> KvResult<Stats> {
+        let log_size = self.log.metadata()?.len();
+        let (compacted, _) = self.compacted_log()?;
+        let live_bytes = compacted.len() as u64;
+        let reclaimable_bytes = log_size.saturating_sub(live_bytes);
+        let garbage_fraction = if log_size == 0 {
+            0.0
+        } else {
+            reclaimable_bytes as f64 / log_size as f64
+        };
+
+        Ok(Stats {
+            live_keys: self.index.len(),
+            log_size,
+            live_bytes,
+            reclaimable_bytes,
+            garbage_fraction,
+        })
+    }
 
-        writeln!(&self.log, "{}", json)?;
+    pub fn import<R: Read>(&mut self, reader: R) -> KvResult<usize> {
+        let reader = BufReader::new(reader);
+        let saved_threshold = self.compaction_threshold;
+        self.compaction_threshold = usize::MAX;
 
-        self.log_length += 1;
+        let result = self.import_lines(reader);
 
-        let end_offset = self.log.seek(SeekFrom::End(0))?;
+        self.compaction_threshold = saved_threshold;
+        self.compact()?;
 
-        Ok((start_offset, end_offset))
+        result
     }
 
-    fn build_index(log: &File) -> KvResult<(HashMap<String, (u64, u64)>, usize)> {
-        let f = BufReader::new(log);
-        let mut index: HashMap<String, (u64, u64)> = HashMap::new();
-        let mut i1: usize = 0;
-        let mut log_length: usize = 0;
+    fn import_lines<R: Read>(&mut self, reader: BufReader<R>) -> KvResult<usize> {
+        let mut count = 0;
 
-        for maybe_line in f.lines() {
+        for maybe_line in reader.lines() {
             let line = maybe_line?;
-            let i0 = i1;
 
-            i1 += line.len();
+            if line.is_empty() {
+                continue;
+            }
 
             match serde_json::from_str(&line)? {
-                Command::Set { key, .. } => index.insert(key.to_owned(), (i0 as u64, i1 as u64)),
-                Command::Rm { key } => index.remove(&key),
-            };
+                BulkCommand::Set { key, value } => self.set(key, value)?,
+                BulkCommand::Rm { key } => self.remove(key)?,
+            }
 
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    pub fn export<W: Write>(&mut self, mut writer: W) -> KvResult<usize> {
+        let keys: Vec<String> = self.index.keys().cloned().collect();
+        let mut count = 0;
+
+        for key in keys {
+            if let Some(value) = self.get(key.clone())? {
+                serde_json::to_writer(&mut writer, &BulkCommand::Set { key, value })?;
+                writeln!(writer)?;
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    fn append_command(&mut self, command: Command) -> KvResult<(u64, u64)> {
+        let tag = match command {
+            Command::Set { .. } => TAG_SET,
+            Command::Rm { .. } => TAG_RM,
+        };
+        let payload = serde_json::to_vec(&command)?;
+
+        self.append_record(tag, &payload)
+    }
+
+    fn append_blob(&mut self, value: &[u8]) -> KvResult<(u64, u64)> {
+        self.append_record(TAG_BLOB, value)
+    }
+
+    fn append_record(&mut self, tag: u8, payload: &[u8]) -> KvResult<(u64, u64)> {
+        let payload_len = payload.len() as u32;
+
+        self.log.seek(SeekFrom::End(0))?;
+        self.log.write_all(&[tag])?;
+        self.log.write_all(&payload_len.to_le_bytes())?;
+
+        let payload_start = self.log.stream_position()?;
+        self.log.write_all(payload)?;
+        let payload_end = self.log.stream_position()?;
+
+        self.log_length += 1;
+
+        Ok((payload_start, payload_end))
+    }
+
+    fn build_index(log: &File) -> KvResult<BuildIndexResult> {
+        let mut f = BufReader::new(log);
+        let mut index: HashMap<String, ValueHash> = HashMap::new();
+        let mut value_locations: HashMap<ValueHash, (u64, u64)> = HashMap::new();
+        let mut log_length: usize = 0;
+        let mut offset: u64 = 0;
+
+        loop {
+            let mut header = [0u8; RECORD_HEADER_LEN as usize];
+
+            match f.read_exact(&mut header) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+
+            let mut len_bytes = [0u8; 4];
+            len_bytes.copy_from_slice(&header[1..5]);
+            let payload_len = u32::from_le_bytes(len_bytes) as u64;
+            let payload_start = offset + RECORD_HEADER_LEN;
+            let payload_end = payload_start + payload_len;
+
+            let mut payload = vec![0u8; payload_len as usize];
+            f.read_exact(&mut payload)?;
+
+            if header[0] == TAG_BLOB {
+                value_locations.insert(hash_value(&payload), (payload_start, payload_end));
+            } else {
+                match serde_json::from_slice(&payload)? {
+                    Command::Set { key, value_ref } => index.insert(key, value_ref),
+                    Command::Rm { key } => index.remove(&key),
+                };
+            }
+
+            offset = payload_end;
             log_length += 1;
         }
 
-        Ok((index, log_length))
+        Ok((index, value_locations, log_length))
     }
 
-    fn compact(&mut self) -> KvResult<()> {
-        let compacted_len: u64 = self.index.values().map(|(i0, i1)| i1 - i0).sum();
-        let mut compacted_log = vec![0u8; compacted_len as usize];
-        let mut i = 0;
+    pub fn compact(&mut self) -> KvResult<()> {
+        let (compacted, new_value_locations) = self.compacted_log()?;
+        let live_hashes: HashSet<ValueHash> = self.index.values().cloned().collect();
+
+        let log_path = self.dir_path.join("log");
+        let tmp_path = self.dir_path.join("log.compact");
 
-        for (i0, i1) in self.index.values() {
-            let command_len = (i1 - i0) as usize;
+        fs::write(&tmp_path, &compacted)?;
+        fs::rename(&tmp_path, &log_path)?;
 
-            self.log.seek(SeekFrom::Start(*i0))?;
-            self.log
-                .read_exact(&mut compacted_log[i..(i + command_len)])?;
+        self.log = OpenOptions::new()
+            .read(true)
+            .create(true)
+            .append(true)
+            .open(&log_path)?;
+        self.value_locations = new_value_locations;
+        self.log_length = live_hashes.len() + self.index.len();
 
-            i += command_len;
+        Ok(())
+    }
+
+    // Builds the log bytes that `compact` would write: one blob record per
+    // hash still referenced by the index, followed by one `Set` pointer
+    // record per live key. Also used by `stats` so "reclaimable bytes"
+    // reflects exactly what compaction can recover, including per-record
+    // header and pointer overhead, rather than just deduplicated blob bytes.
+    fn compacted_log(&self) -> KvResult<CompactedLog> {
+        let mut compacted = Vec::new();
+        let mut new_value_locations: HashMap<ValueHash, (u64, u64)> = HashMap::new();
+        let live_hashes: HashSet<ValueHash> = self.index.values().cloned().collect();
+        let mut log = &self.log;
+
+        for hash in &live_hashes {
+            let (i0, i1) = self.value_locations[hash];
+            let payload_len = (i1 - i0) as usize;
+            let mut payload = vec![0u8; payload_len];
+
+            log.seek(SeekFrom::Start(i0))?;
+            log.read_exact(&mut payload)?;
+
+            let payload_start = compacted.len() as u64 + RECORD_HEADER_LEN;
+            compacted.push(TAG_BLOB);
+            compacted.extend_from_slice(&(payload_len as u32).to_le_bytes());
+            compacted.extend_from_slice(&payload);
+
+            new_value_locations.insert(*hash, (payload_start, payload_start + payload_len as u64));
+        }
+
+        for (key, hash) in &self.index {
+            let payload = serde_json::to_vec(&Command::Set {
+                key: key.clone(),
+                value_ref: *hash,
+            })?;
+
+            compacted.push(TAG_SET);
+            compacted.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            compacted.extend_from_slice(&payload);
+        }
+
+        Ok((compacted, new_value_locations))
+    }
+
+    pub fn snapshot(&mut self, path: impl AsRef<Path>) -> KvResult<()> {
+        let mut keys: Vec<String> = self.index.keys().cloned().collect();
+        keys.sort();
+
+        let mut names = Vec::new();
+        let mut name_spans = Vec::with_capacity(keys.len());
+
+        for key in &keys {
+            name_spans.push((names.len() as u32, key.len() as u32));
+            names.extend_from_slice(key.as_bytes());
+        }
+
+        let data_start =
+            ARCHIVE_HEADER_LEN + ARCHIVE_ENTRY_LEN * keys.len() as u64 + names.len() as u64;
+
+        let mut data = Vec::new();
+        let mut data_spans = Vec::with_capacity(keys.len());
+
+        for key in &keys {
+            let value = self.get(key.clone())?.unwrap_or_default();
+            data_spans.push((data_start + data.len() as u64, value.len() as u64));
+            data.extend_from_slice(value.as_bytes());
         }
 
-        self.log.seek(SeekFrom::Start(0))?;
-        self.log.set_len(compacted_len)?;
-        self.log.write(&compacted_log)?;
+        let mut archive = Vec::new();
+        archive.extend_from_slice(ARCHIVE_MAGIC);
+        archive.extend_from_slice(&(keys.len() as u32).to_le_bytes());
+
+        for ((name_offset, name_len), (data_offset, data_len)) in
+            name_spans.iter().zip(data_spans.iter())
+        {
+            archive.extend_from_slice(&name_offset.to_le_bytes());
+            archive.extend_from_slice(&name_len.to_le_bytes());
+            archive.extend_from_slice(&data_offset.to_le_bytes());
+            archive.extend_from_slice(&data_len.to_le_bytes());
+        }
+
+        archive.extend_from_slice(&names);
+        archive.extend_from_slice(&data);
+
+        fs::write(path, &archive)?;
 
         Ok(())
     }
 }
 
+pub struct Archive {
+    file: File,
+    entries: Vec<(String, u64, u64)>,
+}
+
+impl Archive {
+    pub fn open_archive(path: impl AsRef<Path>) -> KvResult<Archive> {
+        let mut file = File::open(path)?;
+
+        let mut header = [0u8; ARCHIVE_HEADER_LEN as usize];
+        file.read_exact(&mut header)?;
+
+        if &header[0..4] != ARCHIVE_MAGIC.as_slice() {
+            return Err(KvError::InvalidArchive);
+        }
+
+        let mut count_bytes = [0u8; 4];
+        count_bytes.copy_from_slice(&header[4..8]);
+        let entry_count = u32::from_le_bytes(count_bytes) as u64;
+
+        let mut directory = vec![0u8; (ARCHIVE_ENTRY_LEN * entry_count) as usize];
+        file.read_exact(&mut directory)?;
+
+        let raw_entries: Vec<(u32, u32, u64, u64)> = directory
+            .chunks_exact(ARCHIVE_ENTRY_LEN as usize)
+            .map(|raw_entry| {
+                (
+                    read_u32(&raw_entry[0..4]),
+                    read_u32(&raw_entry[4..8]),
+                    read_u64(&raw_entry[8..16]),
+                    read_u64(&raw_entry[16..24]),
+                )
+            })
+            .collect();
+
+        // Names are written contiguously in directory order, so the names
+        // section ends where the furthest name span ends.
+        let names_len = raw_entries
+            .iter()
+            .map(|(name_offset, name_len, _, _)| (name_offset + name_len) as usize)
+            .max()
+            .unwrap_or(0);
+        let mut names = vec![0u8; names_len];
+        file.read_exact(&mut names)?;
+
+        let mut entries = Vec::with_capacity(entry_count as usize);
+
+        for (name_offset, name_len, data_offset, data_len) in raw_entries {
+            let (name_offset, name_len) = (name_offset as usize, name_len as usize);
+            let key = String::from_utf8(names[name_offset..name_offset + name_len].to_vec())?;
+
+            entries.push((key, data_offset, data_len));
+        }
+
+        Ok(Archive { file, entries })
+    }
+
+    pub fn get(&mut self, key: &str) -> KvResult<Option<String>> {
+        match self.entries.binary_search_by(|(k, _, _)| k.as_str().cmp(key)) {
+            Err(_) => Ok(None),
+            Ok(i) => {
+                let (_, offset, length) = self.entries[i];
+                let mut buf = vec![0u8; length as usize];
+
+                self.file.seek(SeekFrom::Start(offset))?;
+                self.file.read_exact(&mut buf)?;
+
+                Ok(Some(String::from_utf8(buf)?))
+            }
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(bytes);
+    u32::from_le_bytes(buf)
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(bytes);
+    u64::from_le_bytes(buf)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -187,6 +561,19 @@ mod tests {
         assert_eq!(kv.get("k1".to_owned()).unwrap().unwrap(), "v1".to_owned());
     }
 
+    #[test]
+    fn value_with_embedded_newlines_survives_reopen() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut kv = KvStore::open(temp_dir.path()).unwrap();
+        let value = "line one\nline two\nline three".to_owned();
+
+        kv.set("k1".to_owned(), value.clone()).unwrap();
+        drop(kv);
+
+        let mut kv = KvStore::open(temp_dir.path()).unwrap();
+        assert_eq!(kv.get("k1".to_owned()).unwrap().unwrap(), value);
+    }
+
     #[test]
     fn multiple_write_then_read() {
         let temp_dir = TempDir::new().unwrap();
@@ -235,6 +622,118 @@ mod tests {
         assert_eq!(kv.get("k1".to_owned()).unwrap(), None);
     }
 
+    #[test]
+    fn import_applies_sets_and_removes() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut kv = KvStore::open(temp_dir.path()).unwrap();
+        let input = "{\"set\":{\"key\":\"k1\",\"value\":\"v1\"}}\n\
+                     {\"set\":{\"key\":\"k2\",\"value\":\"v2\"}}\n\
+                     {\"rm\":{\"key\":\"k1\"}}\n";
+
+        let count = kv.import(input.as_bytes()).unwrap();
+
+        assert_eq!(count, 3);
+        assert_eq!(kv.get("k1".to_owned()).unwrap(), None);
+        assert_eq!(kv.get("k2".to_owned()).unwrap().unwrap(), "v2".to_owned());
+    }
+
+    #[test]
+    fn import_restores_compaction_threshold_after_malformed_line() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut kv = KvStore::open(temp_dir.path()).unwrap();
+        kv.compaction_threshold = 5;
+        let input = "{\"set\":{\"key\":\"k1\",\"value\":\"v1\"}}\nnot json\n";
+
+        assert!(kv.import(input.as_bytes()).is_err());
+
+        assert_eq!(kv.compaction_threshold, 5);
+        assert_eq!(kv.get("k1".to_owned()).unwrap().unwrap(), "v1".to_owned());
+    }
+
+    #[test]
+    fn export_then_import_round_trips() {
+        let temp_dir_a = TempDir::new().unwrap();
+        let mut kv_a = KvStore::open(temp_dir_a.path()).unwrap();
+
+        kv_a.set("k1".to_owned(), "v1".to_owned()).unwrap();
+        kv_a.set("k2".to_owned(), "v2".to_owned()).unwrap();
+
+        let mut exported = Vec::new();
+        let count = kv_a.export(&mut exported).unwrap();
+        assert_eq!(count, 2);
+
+        let temp_dir_b = TempDir::new().unwrap();
+        let mut kv_b = KvStore::open(temp_dir_b.path()).unwrap();
+        kv_b.import(exported.as_slice()).unwrap();
+
+        assert_eq!(kv_b.get("k1".to_owned()).unwrap().unwrap(), "v1".to_owned());
+        assert_eq!(kv_b.get("k2".to_owned()).unwrap().unwrap(), "v2".to_owned());
+    }
+
+    #[test]
+    fn duplicate_values_share_one_blob() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut kv = KvStore::open(temp_dir.path()).unwrap();
+
+        kv.set("k1".to_owned(), "shared".to_owned()).unwrap();
+        kv.set("k2".to_owned(), "shared".to_owned()).unwrap();
+        kv.set("k3".to_owned(), "different".to_owned()).unwrap();
+
+        assert_eq!(kv.value_locations.len(), 2);
+        assert_eq!(kv.get("k1".to_owned()).unwrap().unwrap(), "shared".to_owned());
+        assert_eq!(kv.get("k2".to_owned()).unwrap().unwrap(), "shared".to_owned());
+        assert_eq!(
+            kv.get("k3".to_owned()).unwrap().unwrap(),
+            "different".to_owned()
+        );
+
+        kv.remove("k1".to_owned()).unwrap();
+        assert_eq!(kv.get("k2".to_owned()).unwrap().unwrap(), "shared".to_owned());
+    }
+
+    #[test]
+    fn short_values_keep_the_log_compact() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut kv = KvStore::open(temp_dir.path()).unwrap();
+
+        kv.set("k1".to_owned(), "v1".to_owned()).unwrap();
+
+        assert!(kv.log.metadata().unwrap().len() < 120);
+    }
+
+    #[test]
+    fn stats_reports_live_and_reclaimable_bytes() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut kv = KvStore::open(temp_dir.path()).unwrap();
+
+        kv.set("k1".to_owned(), "v1".to_owned()).unwrap();
+        kv.set("k1".to_owned(), "v2".to_owned()).unwrap();
+        kv.remove("k1".to_owned()).unwrap();
+        kv.set("k2".to_owned(), "v3".to_owned()).unwrap();
+
+        let stats = kv.stats().unwrap();
+
+        assert_eq!(stats.live_keys, 1);
+        assert_eq!(stats.log_size, kv.log.metadata().unwrap().len());
+        assert!(stats.live_bytes > 0);
+        assert!(stats.reclaimable_bytes > 0);
+        assert_eq!(stats.live_bytes + stats.reclaimable_bytes, stats.log_size);
+    }
+
+    #[test]
+    fn stats_reports_zero_garbage_with_no_overwrites_or_removes() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut kv = KvStore::open(temp_dir.path()).unwrap();
+
+        kv.set("k1".to_owned(), "v1".to_owned()).unwrap();
+        kv.set("k2".to_owned(), "v2".to_owned()).unwrap();
+
+        let stats = kv.stats().unwrap();
+
+        assert_eq!(stats.reclaimable_bytes, 0);
+        assert_eq!(stats.garbage_fraction, 0.0);
+    }
+
     #[test]
     fn compaction_reduces_logsize() {
         let temp_dir = TempDir::new().unwrap();
@@ -247,18 +746,57 @@ mod tests {
         kv.set("k1".to_owned(), "v3".to_owned()).unwrap();
         kv.set("k1".to_owned(), "v4".to_owned()).unwrap();
 
-        let mut log_a = String::new();
+        let mut log_a = Vec::new();
 
         kv.log.seek(SeekFrom::Start(0)).unwrap();
-        kv.log.read_to_string(&mut log_a).unwrap();
+        kv.log.read_to_end(&mut log_a).unwrap();
 
         kv.set("k1".to_owned(), "v5".to_owned()).unwrap();
 
-        let mut log_b = String::new();
+        let mut log_b = Vec::new();
 
         kv.log.seek(SeekFrom::Start(0)).unwrap();
-        kv.log.read_to_string(&mut log_b).unwrap();
+        kv.log.read_to_end(&mut log_b).unwrap();
 
         assert!(log_b.len() < log_a.len());
     }
+
+    #[test]
+    fn compact_preserves_all_keys_after_overwrite() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut kv = KvStore::open(temp_dir.path()).unwrap();
+
+        kv.set("k1".to_owned(), "v1".to_owned()).unwrap();
+        kv.set("k2".to_owned(), "v2".to_owned()).unwrap();
+        kv.set("k1".to_owned(), "v1-overwritten".to_owned()).unwrap();
+
+        kv.compact().unwrap();
+
+        assert_eq!(
+            kv.get("k1".to_owned()).unwrap().unwrap(),
+            "v1-overwritten".to_owned()
+        );
+        assert_eq!(kv.get("k2".to_owned()).unwrap().unwrap(), "v2".to_owned());
+    }
+
+    #[test]
+    fn snapshot_then_open_archive_round_trips_every_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut kv = KvStore::open(temp_dir.path()).unwrap();
+
+        kv.set("k1".to_owned(), "v1".to_owned()).unwrap();
+        kv.set("k2".to_owned(), "v2".to_owned()).unwrap();
+        kv.set("k3".to_owned(), "v1".to_owned()).unwrap();
+        kv.remove("k2".to_owned()).unwrap();
+
+        let archive_path = temp_dir.path().join("snapshot.far");
+        kv.snapshot(&archive_path).unwrap();
+
+        let mut archive = Archive::open_archive(&archive_path).unwrap();
+
+        assert_eq!(archive.get("k1").unwrap().unwrap(), "v1".to_owned());
+        assert_eq!(archive.get("k3").unwrap().unwrap(), "v1".to_owned());
+        assert_eq!(archive.get("k2").unwrap(), None);
+        assert_eq!(archive.get("missing").unwrap(), None);
+    }
 }
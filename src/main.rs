@@ -1,4 +1,6 @@
 use kv::{KvError, KvStore};
+use std::fs::File;
+use std::io;
 use std::path::PathBuf;
 
 use structopt::StructOpt;
@@ -11,6 +13,25 @@ enum Command {
     Set { key: String, value: String },
     #[structopt(name = "rm", about = "Remove the value for a key")]
     Remove { key: String },
+    #[structopt(name = "import", about = "Bulk-load key/value pairs from a JSONL file")]
+    Import {
+        #[structopt(parse(from_os_str), help = "JSONL file to read, or stdin if omitted")]
+        file: Option<PathBuf>,
+    },
+    #[structopt(name = "export", about = "Bulk-dump all key/value pairs to a JSONL file")]
+    Export {
+        #[structopt(parse(from_os_str), help = "JSONL file to write, or stdout if omitted")]
+        file: Option<PathBuf>,
+    },
+    #[structopt(name = "stats", about = "Report live vs. reclaimable log bytes")]
+    Stats,
+    #[structopt(name = "compact", about = "Reclaim space used by dead log records")]
+    Compact,
+    #[structopt(name = "snapshot", about = "Write every live key/value pair to a single archive file")]
+    Snapshot {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
 }
 
 #[derive(Debug, StructOpt)]
@@ -33,6 +54,31 @@ fn main() -> Result<(), KvError> {
         },
         Command::Remove { key } => kv.remove(key)?,
         Command::Set { key, value } => kv.set(key, value)?,
+        Command::Import { file } => {
+            let count = match file {
+                Some(path) => kv.import(File::open(path)?)?,
+                None => kv.import(io::stdin().lock())?,
+            };
+            eprintln!("imported {} record(s)", count);
+        }
+        Command::Export { file } => {
+            let count = match file {
+                Some(path) => kv.export(File::create(path)?)?,
+                None => kv.export(io::stdout().lock())?,
+            };
+            eprintln!("exported {} record(s)", count);
+        }
+        Command::Stats => {
+            let stats = kv.stats()?;
+
+            println!("live keys:         {}", stats.live_keys);
+            println!("log size (bytes):  {}", stats.log_size);
+            println!("live bytes:        {}", stats.live_bytes);
+            println!("reclaimable bytes: {}", stats.reclaimable_bytes);
+            println!("garbage fraction:  {:.2}%", stats.garbage_fraction * 100.0);
+        }
+        Command::Compact => kv.compact()?,
+        Command::Snapshot { file } => kv.snapshot(file)?,
     }
 
     Ok(())